@@ -1,11 +1,12 @@
 use crossterm::event::{Event, KeyCode, KeyEvent};
+use crossterm::style::{Attribute, Color, SetAttribute, SetForegroundColor};
 use crossterm::terminal::ClearType;
 use crossterm::{cursor, event, execute, queue, terminal};
-use std::{cmp, env, fs, io};
+use std::{cmp, env, fs, io, mem};
 use std::io::stdout;
 use std::io::Write;
-use std::path::Path;
-use std::time::Duration;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 enum Direction {
     Up,
@@ -15,26 +16,41 @@ enum Direction {
     TopScreen,
     BottomScreen,
     Home,
-    End
+    End,
+    NextWordStart,
+    PrevWordStart,
+    WordEnd
+}
+
+enum Mode {
+    Normal,
+    Insert,
 }
 
 struct CleanUp;
 
 impl Drop for CleanUp {
-    fn drop(&mut self) {        
+    fn drop(&mut self) {
         terminal::disable_raw_mode().expect("Could not turn off raw mode");
         Output::clear_screen().expect("Error");
     }
 }
 
+enum EditorEvent {
+    Key(KeyEvent),
+    Resize(usize, usize),
+}
+
 struct Reader;
 
 impl Reader {
-    fn read_key(&self) -> crossterm::Result<KeyEvent> {
+    fn read_event(&self) -> crossterm::Result<EditorEvent> {
         loop {
             if event::poll(Duration::from_millis(2000))? {
-                if let Event::Key(event) = event::read()? {
-                    return Ok(event);
+                match event::read()? {
+                    Event::Key(event) => return Ok(EditorEvent::Key(event)),
+                    Event::Resize(cols, rows) => return Ok(EditorEvent::Resize(cols as usize, rows as usize)),
+                    _ => {},
                 }
             }
         }
@@ -78,14 +94,18 @@ impl io::Write for EditorContents {
     }
 }
 
+const QUIT_TIMES: usize = 3;
+
 struct Editor {
     reader: Reader,
     output: Output,
+    mode: Mode,
+    quit_times: usize,
 }
 
 impl Editor {
     fn new() -> Self {
-        Self { reader: Reader, output: Output::new() }
+        Self { reader: Reader, output: Output::new(), mode: Mode::Normal, quit_times: QUIT_TIMES }
     }
 
     fn ch_to_dir(ch: char) -> Direction {
@@ -94,6 +114,9 @@ impl Editor {
             'j' => Direction::Down,
             'k' => Direction::Up,
             'l' => Direction::Right,
+            'w' => Direction::NextWordStart,
+            'b' => Direction::PrevWordStart,
+            'e' => Direction::WordEnd,
             _ => unimplemented!()
         }
     }
@@ -108,16 +131,73 @@ impl Editor {
         }
     }
 
-    fn process_keypress(&mut self) -> crossterm::Result<bool> {
-        match self.reader.read_key()? {
+    fn process_event(&mut self) -> crossterm::Result<bool> {
+        match self.reader.read_event()? {
+            EditorEvent::Resize(cols, rows) => {
+                self.output.resize(cols, rows);
+                Ok(true)
+            },
+            EditorEvent::Key(key_event) => self.process_keypress(key_event),
+        }
+    }
+
+    fn process_keypress(&mut self, key_event: KeyEvent) -> crossterm::Result<bool> {
+        let is_quit = matches!(key_event, KeyEvent {
+            code: KeyCode::Char('q'),
+            modifiers: event::KeyModifiers::CONTROL,
+        });
+        if !is_quit {
+            self.quit_times = QUIT_TIMES;
+        }
+
+        match key_event {
             KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: event::KeyModifiers::CONTROL,
-            } => return Ok(false),            
+            } => {
+                if self.output.dirty > 0 {
+                    self.quit_times -= 1;
+                    if self.quit_times > 0 {
+                        self.output.status_message.set_message(format!(
+                            "File has unsaved changes. Press Ctrl-Q {} more times to quit.",
+                            self.quit_times
+                        ));
+                        return Ok(true);
+                    }
+                }
+                return Ok(false);
+            },
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                modifiers: event::KeyModifiers::CONTROL,
+            } => self.output.save()?,
+            KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: event::KeyModifiers::CONTROL,
+            } => self.output.undo(),
+            KeyEvent {
+                code: KeyCode::Char('y'),
+                modifiers: event::KeyModifiers::CONTROL,
+            } => self.output.redo(),
+            _ => match self.mode {
+                Mode::Normal => self.process_normal_keypress(key_event),
+                Mode::Insert => self.process_insert_keypress(key_event),
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn process_normal_keypress(&mut self, key_event: KeyEvent) {
+        match key_event {
             KeyEvent {
-                code: KeyCode::Char(val @ ('h' | 'j' | 'k' | 'l')),
+                code: KeyCode::Char('i'),
                 modifiers: event::KeyModifiers::NONE,
-            } => self.output.move_cursor(Self::ch_to_dir(val)),            
+            } => self.mode = Mode::Insert,
+            KeyEvent {
+                code: KeyCode::Char(val @ ('h' | 'j' | 'k' | 'l' | 'w' | 'b' | 'e')),
+                modifiers: event::KeyModifiers::NONE,
+            } => self.output.move_cursor(Self::ch_to_dir(val)),
             KeyEvent {
                 code: dir @ (KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right),
                 modifiers: event::KeyModifiers::NONE,
@@ -140,42 +220,105 @@ impl Editor {
             } => self.output.move_cursor(Direction::End),
             _ => {}
         }
+    }
 
-        Ok(true)
+    fn process_insert_keypress(&mut self, key_event: KeyEvent) {
+        match key_event {
+            KeyEvent {
+                code: KeyCode::Esc,
+                ..
+            } => self.mode = Mode::Normal,
+            KeyEvent {
+                code: KeyCode::Char(ch),
+                modifiers: event::KeyModifiers::NONE | event::KeyModifiers::SHIFT,
+            } => self.output.insert_char(ch),
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } => self.output.insert_newline(),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            } => self.output.delete_char(),
+            _ => {}
+        }
     }
 
     fn run (&mut self) -> crossterm::Result<bool> {
         self.output.refresh_screen()?;
-        self.process_keypress()
+        self.process_event()
     }
 }
 
+struct StatusMessage {
+    message: Option<String>,
+    set_time: Option<Instant>,
+}
+
+impl StatusMessage {
+    fn new(initial_message: String) -> Self {
+        Self { message: Some(initial_message), set_time: Some(Instant::now()) }
+    }
+
+    fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+        self.set_time = Some(Instant::now());
+    }
+
+    fn message(&mut self) -> Option<&String> {
+        self.set_time.and_then(|time| {
+            if time.elapsed() > Duration::from_secs(5) {
+                self.message = None;
+                self.set_time = None;
+                None
+            } else {
+                self.message.as_ref()
+            }
+        })
+    }
+}
+
+enum EditOp {
+    Insert { offset: usize, text: String },
+    Delete { offset: usize, text: String },
+}
+
 struct Output {
     win_size: (usize, usize),
     editor_contents: EditorContents,
     cursor_controller: CursorController,
-    editor_rows: EditorRows
+    editor_rows: EditorRows,
+    status_message: StatusMessage,
+    dirty: usize,
+    undo_stack: Vec<EditOp>,
+    redo_stack: Vec<EditOp>,
+    coalesce: bool,
 }
 
 impl Output {
     fn new() -> Self {
         let win_size = terminal::size().map(|(x, y)| (x as usize, y as usize))
                                        .unwrap();
-        Self { 
+        Self {
             win_size,
             editor_contents: EditorContents::new(),
-            cursor_controller: CursorController::new(win_size),
-            editor_rows: EditorRows::new()
+            cursor_controller: CursorController::new((win_size.0, win_size.1 - 2)),
+            editor_rows: EditorRows::new(),
+            status_message: StatusMessage::new("HELP: Ctrl-S = save | Ctrl-Q = quit".into()),
+            dirty: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalesce: false,
         }
     }
 
     fn clear_screen() -> crossterm::Result<()> {
-        execute!(stdout(), terminal::Clear(ClearType::All))?;        
+        execute!(stdout(), terminal::Clear(ClearType::All))?;
         execute!(stdout(), cursor::MoveTo(0, 0))
     }
 
     fn draw_rows(&mut self) {
-        let screen_rows = self.win_size.1;
+        let screen_rows = self.win_size.1 - 2;
         let screen_cols = self.win_size.0;
 
         for j in 0..screen_rows {
@@ -200,8 +343,14 @@ impl Output {
                 }
             }
             else {
-                let len = cmp::min(self.editor_rows.get_row(buffer_row).len(), screen_cols);
-                self.editor_contents.push_str(&self.editor_rows.get_row(buffer_row)[..len])
+                let render = self.editor_rows.get_render(buffer_row);
+                let highlights = self.editor_rows.get_highlights(buffer_row);
+                let col_offset = self.cursor_controller.col_offset;
+                let render_cols = render.chars().count();
+                if render_cols > col_offset {
+                    let end = cmp::min(render_cols, col_offset + screen_cols);
+                    Self::push_highlighted(&mut self.editor_contents, render, highlights, col_offset, end);
+                }
             }
 
             queue!(
@@ -209,22 +358,257 @@ impl Output {
                 terminal::Clear(ClearType::UntilNewLine)
             ).unwrap();
 
-            if j < screen_rows - 1 {
-                self.editor_contents.push_str("\r\n");
+            self.editor_contents.push_str("\r\n");
+        }
+    }
+
+    fn push_highlighted(
+        editor_contents: &mut EditorContents,
+        render: &str,
+        highlights: &[(usize, usize, HlStyle)],
+        start: usize,
+        end: usize,
+    ) {
+        let col_to_byte = |col: usize| render.char_indices().nth(col).map_or(render.len(), |(byte, _)| byte);
+
+        let mut pos = start;
+
+        while pos < end {
+            match highlights.iter().find(|(span_start, len, _)| pos >= *span_start && pos < span_start + len) {
+                Some((span_start, len, style)) => {
+                    let span_end = cmp::min(span_start + len, end);
+                    queue!(editor_contents, SetForegroundColor(style.color())).unwrap();
+                    editor_contents.push_str(&render[col_to_byte(pos)..col_to_byte(span_end)]);
+                    queue!(editor_contents, SetForegroundColor(Color::Reset)).unwrap();
+                    pos = span_end;
+                },
+                None => {
+                    let next_start = highlights.iter()
+                        .map(|(span_start, ..)| *span_start)
+                        .filter(|span_start| *span_start > pos)
+                        .min()
+                        .unwrap_or(end);
+                    let seg_end = cmp::min(next_start, end);
+                    editor_contents.push_str(&render[col_to_byte(pos)..col_to_byte(seg_end)]);
+                    pos = seg_end;
+                },
             }
         }
     }
 
+    fn draw_status_bar(&mut self) {
+        queue!(self.editor_contents, SetAttribute(Attribute::Reverse)).unwrap();
+
+        let file_name = self.editor_rows.file_name
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .and_then(|name| name.to_str())
+            .unwrap_or("[No Name]");
+        let modified = if self.dirty > 0 { " (modified)" } else { "" };
+        let mut info = format!("{} - {} lines{}", file_name, self.editor_rows.number_of_rows(), modified);
+        info.truncate(cmp::min(info.len(), self.win_size.0));
+        self.editor_contents.push_str(&info);
+
+        let line_info = format!("{}/{}", self.cursor_controller.cursor_y + 1, self.editor_rows.number_of_rows());
+
+        while info.len() < self.win_size.0 {
+            if self.win_size.0 - info.len() == line_info.len() {
+                self.editor_contents.push_str(&line_info);
+                break;
+            }
+            self.editor_contents.push(' ');
+            info.push(' ');
+        }
+
+        queue!(self.editor_contents, SetAttribute(Attribute::Reset)).unwrap();
+        self.editor_contents.push_str("\r\n");
+    }
+
+    fn draw_message_bar(&mut self) {
+        queue!(self.editor_contents, terminal::Clear(ClearType::UntilNewLine)).unwrap();
+
+        if let Some(message) = self.status_message.message() {
+            let len = cmp::min(message.len(), self.win_size.0);
+            self.editor_contents.push_str(&message[..len]);
+        }
+    }
+
     fn move_cursor(&mut self, dir: Direction) {
-        self.cursor_controller.move_cursor(dir, self.editor_rows.number_of_rows());
+        self.cursor_controller.move_cursor(dir, &self.editor_rows);
+        self.coalesce = false;
+    }
+
+    fn insert_char(&mut self, ch: char) {
+        let offset = self.editor_rows.offset_of(self.cursor_controller.cursor_x, self.cursor_controller.cursor_y);
+        self.editor_rows.insert_char(self.cursor_controller.cursor_x, self.cursor_controller.cursor_y, ch);
+        self.cursor_controller.cursor_x += 1;
+        self.dirty += 1;
+        self.push_insert(offset, ch.to_string(), true);
+    }
+
+    fn insert_newline(&mut self) {
+        let offset = self.editor_rows.offset_of(self.cursor_controller.cursor_x, self.cursor_controller.cursor_y);
+        self.editor_rows.insert_newline(self.cursor_controller.cursor_x, self.cursor_controller.cursor_y);
+        self.cursor_controller.cursor_y += 1;
+        self.cursor_controller.cursor_x = 0;
+        self.dirty += 1;
+        self.coalesce = false;
+        self.push_insert(offset, "\n".to_string(), false);
+    }
+
+    fn delete_char(&mut self) {
+        if self.cursor_controller.cursor_x == 0 && self.cursor_controller.cursor_y == 0 {
+            return;
+        }
+
+        let y = self.cursor_controller.cursor_y;
+        let x = cmp::min(self.cursor_controller.cursor_x, self.editor_rows.get_row(y).chars().count());
+        let removed = if x == 0 {
+            "\n".to_string()
+        } else {
+            self.editor_rows.get_row(y).chars().nth(x - 1).unwrap().to_string()
+        };
+
+        let prev_row_len = if x == 0 {
+            Some(self.editor_rows.get_row(y - 1).chars().count())
+        } else {
+            None
+        };
+
+        let offset = if x == 0 {
+            self.editor_rows.offset_of(self.editor_rows.get_row(y - 1).chars().count(), y - 1)
+        } else {
+            self.editor_rows.offset_of(x - 1, y)
+        };
+        self.editor_rows.delete_char(x, y);
+
+        match prev_row_len {
+            Some(len) => {
+                self.cursor_controller.cursor_y -= 1;
+                self.cursor_controller.cursor_x = len;
+            },
+            None => self.cursor_controller.cursor_x = x - 1,
+        }
+        self.dirty += 1;
+        self.push_delete(offset, removed);
+    }
+
+    fn push_insert(&mut self, offset: usize, text: String, coalesce: bool) {
+        self.redo_stack.clear();
+
+        if self.coalesce {
+            if let Some(EditOp::Insert { offset: top_offset, text: top_text }) = self.undo_stack.last_mut() {
+                if *top_offset + top_text.len() == offset {
+                    top_text.push_str(&text);
+                    self.coalesce = coalesce;
+                    return;
+                }
+            }
+        }
+
+        self.undo_stack.push(EditOp::Insert { offset, text });
+        self.coalesce = coalesce;
+    }
+
+    fn push_delete(&mut self, offset: usize, text: String) {
+        self.redo_stack.clear();
+
+        if self.coalesce {
+            if let Some(EditOp::Delete { offset: top_offset, text: top_text }) = self.undo_stack.last_mut() {
+                if offset + text.len() == *top_offset {
+                    *top_offset = offset;
+                    let mut merged = text;
+                    merged.push_str(top_text);
+                    *top_text = merged;
+                    self.coalesce = true;
+                    return;
+                }
+            }
+        }
+
+        self.undo_stack.push(EditOp::Delete { offset, text });
+        self.coalesce = true;
+    }
+
+    fn move_to_offset(&mut self, offset: usize) {
+        let (x, y) = self.editor_rows.pos_of(offset);
+        self.cursor_controller.cursor_x = x;
+        self.cursor_controller.cursor_y = y;
+    }
+
+    fn undo(&mut self) {
+        let op = match self.undo_stack.pop() {
+            Some(op) => op,
+            None => return,
+        };
+
+        match &op {
+            EditOp::Insert { offset, text } => {
+                self.editor_rows.apply_delete(*offset, text.len());
+                self.move_to_offset(*offset);
+            },
+            EditOp::Delete { offset, text } => {
+                self.editor_rows.apply_insert(*offset, text);
+                self.move_to_offset(offset + text.len());
+            },
+        }
+
+        self.dirty += 1;
+        self.coalesce = false;
+        self.redo_stack.push(op);
+    }
+
+    fn redo(&mut self) {
+        let op = match self.redo_stack.pop() {
+            Some(op) => op,
+            None => return,
+        };
+
+        match &op {
+            EditOp::Insert { offset, text } => {
+                self.editor_rows.apply_insert(*offset, text);
+                self.move_to_offset(offset + text.len());
+            },
+            EditOp::Delete { offset, text } => {
+                self.editor_rows.apply_delete(*offset, text.len());
+                self.move_to_offset(*offset);
+            },
+        }
+
+        self.dirty += 1;
+        self.coalesce = false;
+        self.undo_stack.push(op);
+    }
+
+    fn save(&mut self) -> crossterm::Result<()> {
+        self.editor_rows.save()?;
+        self.dirty = 0;
+        self.coalesce = false;
+        self.status_message.set_message("File saved".into());
+
+        Ok(())
+    }
+
+    fn resize(&mut self, cols: usize, rows: usize) {
+        self.win_size = (cols, rows);
+        self.cursor_controller.screen_cols = cols;
+        self.cursor_controller.screen_rows = rows - 2;
+
+        let row_idx = cmp::min(self.cursor_controller.cursor_y, self.editor_rows.number_of_rows() - 1);
+        let row = self.editor_rows.get_row(row_idx);
+        self.cursor_controller.scroll(row);
     }
 
     fn refresh_screen(&mut self) -> crossterm::Result<()> {
-        self.cursor_controller.scroll();
+        let row_idx = cmp::min(self.cursor_controller.cursor_y, self.editor_rows.number_of_rows() - 1);
+        let row = self.editor_rows.get_row(row_idx);
+        self.cursor_controller.scroll(row);
         queue!(self.editor_contents, cursor::Hide, cursor::MoveTo(0, 0))?;
         self.draw_rows();
+        self.draw_status_bar();
+        self.draw_message_bar();
 
-        let cursor_x = self.cursor_controller.cursor_x;
+        let cursor_x = self.cursor_controller.render_x - self.cursor_controller.col_offset;
         let cursor_y = self.cursor_controller.cursor_y - self.cursor_controller.row_offset;
         queue!(self.editor_contents, cursor::MoveTo(cursor_x as u16, cursor_y as u16), cursor::Show)?;
         self.editor_contents.flush()
@@ -234,37 +618,58 @@ impl Output {
 struct CursorController {
     cursor_x: usize,
     cursor_y: usize,
+    render_x: usize,
     screen_cols: usize,
     screen_rows: usize,
-    row_offset: usize
+    row_offset: usize,
+    col_offset: usize
 }
 
 impl CursorController {
     fn new(win_size: (usize, usize)) -> CursorController {
-        Self { 
-            cursor_x: 0, 
-            cursor_y: 0, 
-            screen_cols: 
-            win_size.0, 
+        Self {
+            cursor_x: 0,
+            cursor_y: 0,
+            render_x: 0,
+            screen_cols:
+            win_size.0,
             screen_rows: win_size.1,
-            row_offset: 0, 
+            row_offset: 0,
+            col_offset: 0,
+        }
+    }
+
+    fn cursor_x_to_render_x(row: &str, cursor_x: usize) -> usize {
+        let mut render_x = 0;
+        for ch in row.chars().take(cursor_x) {
+            if ch == '\t' {
+                render_x += (TAB_STOP - 1) - (render_x % TAB_STOP);
+            }
+            render_x += 1;
         }
+
+        render_x
     }
 
-    fn scroll(&mut self) {
+    fn scroll(&mut self, row: &str) {
+        self.render_x = Self::cursor_x_to_render_x(row, self.cursor_x);
+
         self.row_offset = cmp::min(self.row_offset, self.cursor_y);
         if self.cursor_y >= self.row_offset + self.screen_rows {
-            //let mut msg = format!("cursor_y: {} screen_rows: {}", self.cursor_y, self.screen_rows);
-            //panic!("{}", msg);
             self.row_offset = self.cursor_y - (self.screen_rows - 1);
         }
+
+        self.col_offset = cmp::min(self.col_offset, self.render_x);
+        if self.render_x >= self.col_offset + self.screen_cols {
+            self.col_offset = self.render_x - self.screen_cols + 1;
+        }
     }
 
-    fn move_cursor(&mut self, dir: Direction, number_of_rows: usize) {
+    fn move_cursor(&mut self, dir: Direction, editor_rows: &EditorRows) {
         match dir {
             Direction::Up => { self.cursor_y = self.cursor_y.saturating_sub(1) },
-            Direction::Down => { 
-                if self.cursor_y < number_of_rows {
+            Direction::Down => {
+                if self.cursor_y < editor_rows.number_of_rows() {
                     self.cursor_y += 1;
                 }
             },
@@ -273,42 +678,551 @@ impl CursorController {
             Direction::TopScreen => { self.cursor_y = 0 },
             Direction::BottomScreen => { self.cursor_y = self.screen_rows - 1 }
             Direction::Home => { self.cursor_x = 0 },
-            Direction::End => { self.cursor_x = self.screen_cols - 1 }
+            Direction::End => { self.cursor_x = self.screen_cols - 1 },
+            Direction::NextWordStart => self.next_word_start(editor_rows),
+            Direction::PrevWordStart => self.prev_word_start(editor_rows),
+            Direction::WordEnd => self.word_end(editor_rows),
+        }
+    }
+
+    fn class_at(y: usize, x: usize, editor_rows: &EditorRows) -> CharClass {
+        editor_rows.get_row(y).chars().nth(x).map(classify_char).unwrap_or(CharClass::Whitespace)
+    }
+
+    fn step_forward(y: usize, x: usize, editor_rows: &EditorRows) -> Option<(usize, usize)> {
+        if x < editor_rows.get_row(y).chars().count() {
+            Some((y, x + 1))
+        } else if y + 1 < editor_rows.number_of_rows() {
+            Some((y + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn step_back(y: usize, x: usize, editor_rows: &EditorRows) -> Option<(usize, usize)> {
+        if x > 0 {
+            Some((y, x - 1))
+        } else if y > 0 {
+            Some((y - 1, editor_rows.get_row(y - 1).chars().count()))
+        } else {
+            None
         }
     }
+
+    fn next_word_start(&mut self, editor_rows: &EditorRows) {
+        self.cursor_y = cmp::min(self.cursor_y, editor_rows.number_of_rows() - 1);
+        let mut pos = (self.cursor_y, self.cursor_x);
+        let start_class = Self::class_at(pos.0, pos.1, editor_rows);
+
+        if start_class != CharClass::Whitespace {
+            while Self::class_at(pos.0, pos.1, editor_rows) == start_class {
+                match Self::step_forward(pos.0, pos.1, editor_rows) {
+                    Some(next) => pos = next,
+                    None => { self.cursor_y = pos.0; self.cursor_x = pos.1; return; }
+                }
+            }
+        }
+
+        while Self::class_at(pos.0, pos.1, editor_rows) == CharClass::Whitespace {
+            match Self::step_forward(pos.0, pos.1, editor_rows) {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+
+        self.cursor_y = pos.0;
+        self.cursor_x = pos.1;
+    }
+
+    fn prev_word_start(&mut self, editor_rows: &EditorRows) {
+        self.cursor_y = cmp::min(self.cursor_y, editor_rows.number_of_rows() - 1);
+        let mut pos = match Self::step_back(self.cursor_y, self.cursor_x, editor_rows) {
+            Some(prev) => prev,
+            None => return,
+        };
+
+        while Self::class_at(pos.0, pos.1, editor_rows) == CharClass::Whitespace {
+            match Self::step_back(pos.0, pos.1, editor_rows) {
+                Some(prev) => pos = prev,
+                None => { self.cursor_y = pos.0; self.cursor_x = pos.1; return; }
+            }
+        }
+
+        let class = Self::class_at(pos.0, pos.1, editor_rows);
+        loop {
+            match Self::step_back(pos.0, pos.1, editor_rows) {
+                Some(prev) if prev.0 == pos.0 && Self::class_at(prev.0, prev.1, editor_rows) == class => pos = prev,
+                _ => break,
+            }
+        }
+
+        self.cursor_y = pos.0;
+        self.cursor_x = pos.1;
+    }
+
+    fn word_end(&mut self, editor_rows: &EditorRows) {
+        self.cursor_y = cmp::min(self.cursor_y, editor_rows.number_of_rows() - 1);
+        let mut pos = match Self::step_forward(self.cursor_y, self.cursor_x, editor_rows) {
+            Some(next) => next,
+            None => return,
+        };
+
+        while Self::class_at(pos.0, pos.1, editor_rows) == CharClass::Whitespace {
+            match Self::step_forward(pos.0, pos.1, editor_rows) {
+                Some(next) => pos = next,
+                None => { self.cursor_y = pos.0; self.cursor_x = pos.1; return; }
+            }
+        }
+
+        let class = Self::class_at(pos.0, pos.1, editor_rows);
+        loop {
+            match Self::step_forward(pos.0, pos.1, editor_rows) {
+                Some(next) if next.0 == pos.0 && Self::class_at(next.0, next.1, editor_rows) == class => pos = next,
+                _ => break,
+            }
+        }
+
+        self.cursor_y = pos.0;
+        self.cursor_x = pos.1;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn classify_char(ch: char) -> CharClass {
+    if ch.is_whitespace() {
+        CharClass::Whitespace
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BufferKind {
+    Original,
+    Add,
+}
+
+#[derive(Clone, Copy)]
+struct Piece {
+    buffer: BufferKind,
+    start: usize,
+    len: usize,
+}
+
+// Holds the original file text untouched and an append-only buffer for
+// everything typed since, stitched together by an ordered list of pieces
+// so edits don't require rewriting the whole document.
+struct PieceTable {
+    original: String,
+    add: String,
+    pieces: Vec<Piece>,
+}
+
+impl PieceTable {
+    fn new(original: String) -> Self {
+        let pieces = if original.is_empty() {
+            Vec::new()
+        } else {
+            vec![Piece { buffer: BufferKind::Original, start: 0, len: original.len() }]
+        };
+
+        Self { original, add: String::new(), pieces }
+    }
+
+    fn piece_text(&self, piece: &Piece) -> &str {
+        let buffer = match piece.buffer {
+            BufferKind::Original => &self.original,
+            BufferKind::Add => &self.add,
+        };
+
+        &buffer[piece.start..piece.start + piece.len]
+    }
+
+    fn text(&self) -> String {
+        let mut out = String::new();
+        for piece in &self.pieces {
+            out.push_str(self.piece_text(piece));
+        }
+
+        out
+    }
+
+    fn insert(&mut self, offset: usize, text: &str) {
+        let new_piece = Piece { buffer: BufferKind::Add, start: self.add.len(), len: text.len() };
+        self.add.push_str(text);
+
+        let mut pos = 0;
+        for idx in 0..self.pieces.len() {
+            let piece = self.pieces[idx];
+            if offset <= pos + piece.len {
+                let split = offset - pos;
+                let mut replacement = Vec::with_capacity(3);
+                if split > 0 {
+                    replacement.push(Piece { buffer: piece.buffer, start: piece.start, len: split });
+                }
+                replacement.push(new_piece);
+                if split < piece.len {
+                    replacement.push(Piece { buffer: piece.buffer, start: piece.start + split, len: piece.len - split });
+                }
+
+                self.pieces.splice(idx..=idx, replacement);
+                return;
+            }
+            pos += piece.len;
+        }
+
+        self.pieces.push(new_piece);
+    }
+
+    fn delete(&mut self, offset: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let end = offset + len;
+        let mut pos = 0;
+        let mut trimmed = Vec::with_capacity(self.pieces.len());
+
+        for piece in &self.pieces {
+            let piece_start = pos;
+            let piece_end = pos + piece.len;
+            pos = piece_end;
+
+            if piece_end <= offset || piece_start >= end {
+                trimmed.push(*piece);
+                continue;
+            }
+
+            if piece_start < offset {
+                trimmed.push(Piece { buffer: piece.buffer, start: piece.start, len: offset - piece_start });
+            }
+            if piece_end > end {
+                trimmed.push(Piece {
+                    buffer: piece.buffer,
+                    start: piece.start + (end - piece_start),
+                    len: piece_end - end,
+                });
+            }
+        }
+
+        self.pieces = trimmed;
+    }
+}
+
+const TAB_STOP: usize = 8;
+
+#[derive(Clone, Copy)]
+enum HlStyle {
+    Number,
+    Str,
+    Comment,
+    Keyword,
+}
+
+impl HlStyle {
+    fn color(&self) -> Color {
+        match self {
+            HlStyle::Number => Color::Magenta,
+            HlStyle::Str => Color::Green,
+            HlStyle::Comment => Color::DarkGrey,
+            HlStyle::Keyword => Color::Yellow,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Syntax {
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+}
+
+const PLAIN_TEXT_SYNTAX: Syntax = Syntax { keywords: &[], line_comment: None };
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while",
+];
+
+const RUST_SYNTAX: Syntax = Syntax { keywords: RUST_KEYWORDS, line_comment: Some("//") };
+
+fn syntax_for(file_name: Option<&PathBuf>) -> Syntax {
+    match file_name.and_then(|path| path.extension()).and_then(|ext| ext.to_str()) {
+        Some("rs") => RUST_SYNTAX,
+        _ => PLAIN_TEXT_SYNTAX,
+    }
+}
+
+fn highlight_row(render: &str, syntax: &Syntax) -> Vec<(usize, usize, HlStyle)> {
+    let mut spans = Vec::new();
+    let chars: Vec<(usize, char)> = render.char_indices().collect();
+    let len = chars.len();
+    let mut i = 0;
+
+    while i < len {
+        let byte_at = |i: usize| chars.get(i).map_or(render.len(), |(b, _)| *b);
+
+        if let Some(comment) = syntax.line_comment {
+            if render[byte_at(i)..].starts_with(comment) {
+                spans.push((i, len - i, HlStyle::Comment));
+                break;
+            }
+        }
+
+        let ch = chars[i].1;
+
+        if ch == '"' || ch == '\'' {
+            let quote = ch;
+            let start = i;
+            i += 1;
+            while i < len && chars[i].1 != quote {
+                if chars[i].1 == '\\' && i + 1 < len {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i < len {
+                i += 1;
+            }
+            spans.push((start, i - start, HlStyle::Str));
+            continue;
+        }
+
+        if ch.is_ascii_digit() {
+            let start = i;
+            while i < len && (chars[i].1 == '.' || chars[i].1.is_ascii_digit()) {
+                i += 1;
+            }
+            spans.push((start, i - start, HlStyle::Number));
+            continue;
+        }
+
+        if ch.is_alphabetic() || ch == '_' {
+            let start = i;
+            while i < len && (chars[i].1.is_alphanumeric() || chars[i].1 == '_') {
+                i += 1;
+            }
+            if syntax.keywords.contains(&&render[byte_at(start)..byte_at(i)]) {
+                spans.push((start, i - start, HlStyle::Keyword));
+            }
+            continue;
+        }
+
+        i += 1;
+    }
+
+    spans
+}
+
+struct Row {
+    chars: String,
+    render: String,
+    highlights: Vec<(usize, usize, HlStyle)>,
+}
+
+fn char_col_to_byte(s: &str, col: usize) -> usize {
+    s.char_indices().nth(col).map_or(s.len(), |(byte, _)| byte)
+}
+
+fn render_row(chars: &str) -> String {
+    let mut render = String::new();
+    let mut idx = 0;
+
+    for ch in chars.chars() {
+        if ch == '\t' {
+            render.push(' ');
+            idx += 1;
+            while idx % TAB_STOP != 0 {
+                render.push(' ');
+                idx += 1;
+            }
+        } else {
+            render.push(ch);
+            idx += 1;
+        }
+    }
+
+    render
 }
 
 struct EditorRows {
-    row_contents: Vec<Box<str>>
+    piece_table: PieceTable,
+    rows: Vec<Row>,
+    file_name: Option<PathBuf>,
+    syntax: Syntax,
 }
 
 impl EditorRows {
-    fn new() -> Self {        
+    fn new() -> Self {
         let mut arg = env::args();
 
         match arg.nth(1) {
-            None => Self { row_contents: Vec::new() },            
-            Some(file) => Self::from_file(file.as_ref()),
-        }        
+            None => Self {
+                piece_table: PieceTable::new(String::new()),
+                rows: vec![Row { chars: String::new(), render: String::new(), highlights: Vec::new() }],
+                file_name: None,
+                syntax: PLAIN_TEXT_SYNTAX,
+            },
+            Some(file) => Self::from_file(file.into()),
+        }
+    }
+
+    fn from_file(file: PathBuf) -> Self {
+        let contents = fs::read_to_string(&file).expect("Unable to read file");
+        let mut editor_rows = Self {
+            piece_table: PieceTable::new(contents),
+            rows: Vec::new(),
+            syntax: syntax_for(Some(&file)),
+            file_name: Some(file),
+        };
+        editor_rows.rebuild_rows();
+
+        editor_rows
+    }
+
+    fn rebuild_rows(&mut self) {
+        let text = self.piece_table.text();
+        self.rows = text.split('\n').map(|line| self.build_row(line.to_string())).collect();
+    }
+
+    fn build_row(&self, chars: String) -> Row {
+        let render = render_row(&chars);
+        let highlights = highlight_row(&render, &self.syntax);
+        Row { chars, render, highlights }
+    }
+
+    fn set_row(&mut self, index: usize, chars: String) {
+        let row = self.build_row(chars);
+        self.rows[index] = row;
     }
 
-    fn from_file(file: &Path) -> Self {
-        let contents = fs::read_to_string(file).expect("Unable to read file");
-        Self { row_contents: contents.lines().map(|txt| txt.into()).collect() }
+    fn rebuild_row(&mut self, index: usize) {
+        let chars = mem::take(&mut self.rows[index].chars);
+        self.set_row(index, chars);
     }
 
     fn number_of_rows(&self) -> usize {
-        self.row_contents.len()
+        self.rows.len()
     }
 
     fn get_row(&self, n: usize) -> &str {
-        &self.row_contents[n]
+        &self.rows[n].chars
+    }
+
+    fn get_render(&self, n: usize) -> &str {
+        &self.rows[n].render
+    }
+
+    fn get_highlights(&self, n: usize) -> &[(usize, usize, HlStyle)] {
+        &self.rows[n].highlights
+    }
+
+    fn offset_of(&self, cursor_x: usize, cursor_y: usize) -> usize {
+        let mut offset: usize = self.rows[..cursor_y].iter().map(|row| row.chars.len() + 1).sum();
+        offset += char_col_to_byte(&self.rows[cursor_y].chars, cursor_x);
+
+        offset
+    }
+
+    // Insert/delete touch at most the rows the cursor is on, so rebuild just
+    // those rows instead of re-splitting the whole piece table on every
+    // keystroke.
+    fn insert_char(&mut self, cursor_x: usize, cursor_y: usize, ch: char) {
+        let offset = self.offset_of(cursor_x, cursor_y);
+        let mut buf = [0; 4];
+        self.piece_table.insert(offset, ch.encode_utf8(&mut buf));
+
+        let row = &mut self.rows[cursor_y];
+        let byte = char_col_to_byte(&row.chars, cursor_x);
+        row.chars.insert(byte, ch);
+        self.rebuild_row(cursor_y);
+    }
+
+    fn insert_newline(&mut self, cursor_x: usize, cursor_y: usize) {
+        let offset = self.offset_of(cursor_x, cursor_y);
+        self.piece_table.insert(offset, "\n");
+
+        let tail = {
+            let row = &mut self.rows[cursor_y];
+            let byte = char_col_to_byte(&row.chars, cursor_x);
+            row.chars.split_off(byte)
+        };
+        self.rebuild_row(cursor_y);
+        let new_row = self.build_row(tail);
+        self.rows.insert(cursor_y + 1, new_row);
+    }
+
+    fn delete_char(&mut self, cursor_x: usize, cursor_y: usize) {
+        if cursor_x == 0 && cursor_y == 0 {
+            return;
+        }
+
+        let offset = self.offset_of(cursor_x, cursor_y);
+        let prev_offset = if cursor_x == 0 {
+            offset - 1
+        } else {
+            self.offset_of(cursor_x - 1, cursor_y)
+        };
+        self.piece_table.delete(prev_offset, offset - prev_offset);
+
+        if cursor_x == 0 {
+            let removed = self.rows.remove(cursor_y);
+            let prev = cursor_y - 1;
+            let mut merged = mem::take(&mut self.rows[prev].chars);
+            merged.push_str(&removed.chars);
+            self.set_row(prev, merged);
+        } else {
+            let row = &mut self.rows[cursor_y];
+            let start = char_col_to_byte(&row.chars, cursor_x - 1);
+            let end = char_col_to_byte(&row.chars, cursor_x);
+            row.chars.replace_range(start..end, "");
+            self.rebuild_row(cursor_y);
+        }
+    }
+
+    fn save(&self) -> crossterm::Result<()> {
+        match &self.file_name {
+            Some(file_name) => fs::write(file_name, self.piece_table.text()),
+            None => Ok(()),
+        }
+    }
+
+    fn pos_of(&self, offset: usize) -> (usize, usize) {
+        let mut remaining = offset;
+        for (y, row) in self.rows.iter().enumerate() {
+            if remaining <= row.chars.len() {
+                return (row.chars[..remaining].chars().count(), y);
+            }
+            remaining -= row.chars.len() + 1;
+        }
+
+        let last = self.rows.len() - 1;
+        (self.rows[last].chars.chars().count(), last)
+    }
+
+    fn apply_insert(&mut self, offset: usize, text: &str) {
+        self.piece_table.insert(offset, text);
+        self.rebuild_rows();
+    }
+
+    fn apply_delete(&mut self, offset: usize, len: usize) {
+        self.piece_table.delete(offset, len);
+        self.rebuild_rows();
     }
 }
 
 fn main() -> crossterm::Result<()> {
     let _clean_up = CleanUp;
-    
+
     terminal::enable_raw_mode()?;
 
     let mut editor = Editor::new();